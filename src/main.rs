@@ -14,6 +14,24 @@ mod error {
     pub enum Error {
         #[error("error parsing yaml: {0}")]
         DroneYamlError(#[from] serde_yaml::Error),
+
+        #[error("unauthorized: {0}")]
+        Unauthorized(String),
+
+        #[error("error parsing request body: {0}")]
+        RequestBodyError(#[from] serde_json::Error),
+
+        #[error("invalid query string: {0}")]
+        InvalidQuery(#[from] serde_urlencoded::de::Error),
+
+        // strict-mode abort: carries the offending document's 1-based index alongside
+        // the parse error, so handle_rejection can report its line/column.
+        #[error("error parsing yaml document {index}: {source}")]
+        StrictYamlError {
+            index: usize,
+            #[source]
+            source: serde_yaml::Error,
+        },
     }
 
     impl warp::reject::Reject for Error {}
@@ -21,43 +39,295 @@ mod error {
     #[derive(Serialize)]
     struct ErrorResponse {
         message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        document: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        column: Option<usize>,
+    }
+
+    impl ErrorResponse {
+        fn message(message: impl Into<String>) -> Self {
+            ErrorResponse {
+                message: message.into(),
+                document: None,
+                line: None,
+                column: None,
+            }
+        }
     }
 
     pub async fn handle_rejection(err: Rejection) -> std::result::Result<impl Reply, Infallible> {
         let code;
-        let message;
+        let response;
 
         if err.is_not_found() {
             code = StatusCode::NOT_FOUND;
-            message = "Not Found";
-        } else if let Some(_) = err.find::<warp::filters::body::BodyDeserializeError>() {
-            code = StatusCode::BAD_REQUEST;
-            message = "Invalid Body";
+            response = ErrorResponse::message("Not Found");
         } else if let Some(e) = err.find::<Error>() {
             match e {
+                Error::Unauthorized(reason) => {
+                    code = StatusCode::UNAUTHORIZED;
+                    response = ErrorResponse::message(format!("Unauthorized: {}", reason));
+                }
+                Error::RequestBodyError(_) => {
+                    code = StatusCode::BAD_REQUEST;
+                    response = ErrorResponse::message("Invalid Body");
+                }
+                Error::InvalidQuery(_) => {
+                    code = StatusCode::BAD_REQUEST;
+                    response = ErrorResponse::message("Invalid Query");
+                }
+                Error::StrictYamlError { index, source } => {
+                    code = StatusCode::BAD_REQUEST;
+                    let location = source.location();
+                    response = ErrorResponse {
+                        message: format!("error parsing yaml document {}: {}", index, source),
+                        document: Some(*index),
+                        line: location.as_ref().map(|l| l.line()),
+                        column: location.as_ref().map(|l| l.column()),
+                    };
+                }
                 _ => {
                     eprintln!("unhandled application error: {:?}", err);
                     code = StatusCode::INTERNAL_SERVER_ERROR;
-                    message = "Internal Server Error";
+                    response = ErrorResponse::message("Internal Server Error");
                 }
             }
         } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
             code = StatusCode::METHOD_NOT_ALLOWED;
-            message = "Method Not Allowed";
+            response = ErrorResponse::message("Method Not Allowed");
+        } else if let Some(_) = err.find::<warp::reject::PayloadTooLarge>() {
+            code = StatusCode::PAYLOAD_TOO_LARGE;
+            response = ErrorResponse::message("Payload Too Large");
         } else {
             eprintln!("unhandled error: {:?}", err);
             code = StatusCode::INTERNAL_SERVER_ERROR;
-            message = "Internal Server Error";
+            response = ErrorResponse::message("Internal Server Error");
         }
 
-        let json = warp::reply::json(&ErrorResponse {
-            message: message.into(),
-        });
+        let json = warp::reply::json(&response);
 
         Ok(warp::reply::with_status(json, code))
     }
 }
 
+// Verification of Drone's config/convert extension HTTP-signatures, see
+// https://docs.drone.io/extensions/yaml/ for the protocol description.
+mod signature {
+    use crate::error::Error;
+    use hmac::{Hmac, Mac, NewMac};
+    use sha2::{Digest, Sha256};
+
+    type HmacSha256 = Hmac<Sha256>;
+
+    const SECRET_ENV: &str = "DRONE_SHARED_SECRET";
+
+    // Drone always signs over these, regardless of what the client actually sent in
+    // `headers=`; a signature that omits one doesn't cover it and must be rejected,
+    // rather than silently falling back to trusting it.
+    const REQUIRED_SIGNED_HEADERS: [&str; 3] = ["(request-target)", "date", "digest"];
+
+    struct SignatureHeader {
+        headers: Vec<String>,
+        signature: String,
+    }
+
+    fn parse_signature_header(header: &str) -> Option<SignatureHeader> {
+        let mut headers = None;
+        let mut signature = None;
+
+        for field in header.split(',') {
+            let (key, value) = field.split_once('=')?;
+            let value = value.trim_matches('"');
+            match key {
+                "headers" => headers = Some(value.split(' ').map(String::from).collect()),
+                "signature" => signature = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Some(SignatureHeader {
+            headers: headers?,
+            signature: signature?,
+        })
+    }
+
+    fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        if a.len() != b.len() {
+            return false;
+        }
+        a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+
+    // Verifies the `Digest`, `Date` and `Signature` headers Drone attaches to every
+    // extension request against `body`, using the shared secret from `DRONE_SHARED_SECRET`.
+    // `request_target` is the actual path (plus query string, if any) of the incoming
+    // request, e.g. `/convert` or `/convert?strict=true` -- it must match whatever Drone
+    // signed, so callers derive it from the request rather than assuming a fixed route.
+    pub fn verify(
+        request_target: &str,
+        date: &str,
+        digest: &str,
+        signature: &str,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        let secret = std::env::var(SECRET_ENV)
+            .map_err(|_| Error::Unauthorized(format!("{} not set", SECRET_ENV)))?;
+
+        let expected_digest = format!("SHA-256={}", base64::encode(Sha256::digest(body)));
+        if !constant_time_eq(expected_digest.as_bytes(), digest.as_bytes()) {
+            return Err(Error::Unauthorized("digest mismatch".into()));
+        }
+
+        let parsed = parse_signature_header(signature)
+            .ok_or_else(|| Error::Unauthorized("malformed signature header".into()))?;
+
+        for required in REQUIRED_SIGNED_HEADERS {
+            if !parsed.headers.iter().any(|h| h == required) {
+                return Err(Error::Unauthorized(format!(
+                    "signature doesn't cover required header: {}",
+                    required
+                )));
+            }
+        }
+
+        let request_line = format!("post {}", request_target);
+        let mut signing_string = String::new();
+        for (n, name) in parsed.headers.iter().enumerate() {
+            if n > 0 {
+                signing_string.push('\n');
+            }
+            let value = match name.as_str() {
+                "(request-target)" => request_line.as_str(),
+                "date" => date,
+                "digest" => digest,
+                other => {
+                    return Err(Error::Unauthorized(format!(
+                        "unexpected signed header: {}",
+                        other
+                    )))
+                }
+            };
+            signing_string.push_str(name);
+            signing_string.push_str(": ");
+            signing_string.push_str(value);
+        }
+
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+            .map_err(|_| Error::Unauthorized("invalid secret".into()))?;
+        mac.update(signing_string.as_bytes());
+        let expected_signature = base64::encode(mac.finalize().into_bytes());
+
+        if !constant_time_eq(expected_signature.as_bytes(), parsed.signature.as_bytes()) {
+            return Err(Error::Unauthorized("signature mismatch".into()));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const SECRET: &str = "topsecret";
+        const TARGET: &str = "/convert";
+        const DATE: &str = "Mon, 01 Jan 2024 00:00:00 GMT";
+
+        fn digest_for(body: &[u8]) -> String {
+            format!("SHA-256={}", base64::encode(Sha256::digest(body)))
+        }
+
+        fn sign(signed_headers: &str, request_target: &str, date: &str, digest: &str) -> String {
+            let mut signing_string = String::new();
+            for (n, name) in signed_headers.split(' ').enumerate() {
+                if n > 0 {
+                    signing_string.push('\n');
+                }
+                let value = match name {
+                    "(request-target)" => format!("post {}", request_target),
+                    "date" => date.to_string(),
+                    "digest" => digest.to_string(),
+                    other => panic!("unsupported header in test: {}", other),
+                };
+                signing_string.push_str(name);
+                signing_string.push_str(": ");
+                signing_string.push_str(&value);
+            }
+
+            let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+            mac.update(signing_string.as_bytes());
+            base64::encode(mac.finalize().into_bytes())
+        }
+
+        fn signature_header(signed_headers: &str, signature: &str) -> String {
+            format!(
+                "keyId=\"hmac-key\",algorithm=\"hmac-sha256\",headers=\"{}\",signature=\"{}\"",
+                signed_headers, signature
+            )
+        }
+
+        #[test]
+        fn accepts_a_valid_signature() {
+            std::env::set_var(SECRET_ENV, SECRET);
+            let body = b"pipeline: data";
+            let digest = digest_for(body);
+            let signed_headers = "(request-target) date digest";
+            let signature = sign(signed_headers, TARGET, DATE, &digest);
+            let header = signature_header(signed_headers, &signature);
+
+            assert!(verify(TARGET, DATE, &digest, &header, body).is_ok());
+        }
+
+        #[test]
+        fn rejects_a_tampered_body() {
+            std::env::set_var(SECRET_ENV, SECRET);
+            let body = b"pipeline: data";
+            let digest = digest_for(body);
+            let signed_headers = "(request-target) date digest";
+            let signature = sign(signed_headers, TARGET, DATE, &digest);
+            let header = signature_header(signed_headers, &signature);
+
+            assert!(verify(TARGET, DATE, &digest, &header, b"pipeline: tampered").is_err());
+        }
+
+        #[test]
+        fn rejects_a_signature_that_omits_digest_from_headers() {
+            std::env::set_var(SECRET_ENV, SECRET);
+            let body = b"pipeline: data";
+            let digest = digest_for(body);
+            // digest still matches the body, but isn't part of what was actually signed.
+            let signed_headers = "(request-target) date";
+            let signature = sign(signed_headers, TARGET, DATE, &digest);
+            let header = signature_header(signed_headers, &signature);
+
+            assert!(verify(TARGET, DATE, &digest, &header, body).is_err());
+        }
+
+        #[test]
+        fn rejects_a_malformed_signature_header() {
+            std::env::set_var(SECRET_ENV, SECRET);
+            let body = b"pipeline: data";
+            let digest = digest_for(body);
+
+            assert!(verify(TARGET, DATE, &digest, "not a signature header", body).is_err());
+        }
+
+        #[test]
+        fn rejects_when_request_target_does_not_match_what_was_signed() {
+            std::env::set_var(SECRET_ENV, SECRET);
+            let body = b"pipeline: data";
+            let digest = digest_for(body);
+            let signed_headers = "(request-target) date digest";
+            let signature = sign(signed_headers, "/convert?strict=true", DATE, &digest);
+            let header = signature_header(signed_headers, &signature);
+
+            assert!(verify(TARGET, DATE, &digest, &header, body).is_err());
+        }
+    }
+}
+
 mod drone {
     use serde_derive::{Deserialize, Serialize};
     #[derive(Deserialize, Serialize)]
@@ -84,6 +354,11 @@ mod drone {
         #[serde(skip_serializing_if = "Option::is_none")]
         pub parallelism: Option<usize>,
 
+        // maps axis name to its list of values, e.g. `BOARD: [native, nucleo-f103rb]`.
+        // expanded into one instance per element of the cartesian product of all axes.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub matrix: Option<indexmap::IndexMap<String, Vec<serde_yaml::Value>>>,
+
         #[serde(rename = "type")]
         type_: String,
 
@@ -91,10 +366,345 @@ mod drone {
         #[serde(flatten)]
         extra: indexmap::IndexMap<String, serde_yaml::Value>,
     }
+
+    // Returns the cartesian product of all axes in `matrix`, e.g. `{BOARD: [a, b], TOOLCHAIN: [gcc]}`
+    // becomes `[{BOARD: a, TOOLCHAIN: gcc}, {BOARD: b, TOOLCHAIN: gcc}]`.
+    pub fn matrix_combinations(
+        matrix: &indexmap::IndexMap<String, Vec<serde_yaml::Value>>,
+    ) -> Vec<indexmap::IndexMap<String, serde_yaml::Value>> {
+        let mut combos = vec![indexmap::IndexMap::new()];
+
+        for (axis, values) in matrix {
+            let mut next = Vec::with_capacity(combos.len() * values.len());
+            for combo in &combos {
+                for value in values {
+                    let mut combo = combo.clone();
+                    combo.insert(axis.clone(), value.clone());
+                    next.push(combo);
+                }
+            }
+            combos = next;
+        }
+
+        combos
+    }
+
+    // Inserts `vars` into the `environment` mapping of every step in `extra["steps"]`,
+    // creating the mapping if a step doesn't have one yet.
+    pub fn inject_environment(
+        extra: &mut indexmap::IndexMap<String, serde_yaml::Value>,
+        vars: &indexmap::IndexMap<String, serde_yaml::Value>,
+    ) {
+        let steps = match extra.get_mut("steps") {
+            Some(serde_yaml::Value::Sequence(steps)) => steps,
+            _ => return,
+        };
+
+        for step in steps.iter_mut() {
+            let step = match step {
+                serde_yaml::Value::Mapping(step) => step,
+                _ => continue,
+            };
+
+            let env_key = serde_yaml::Value::String("environment".to_string());
+            let mut env = match step.remove(&env_key) {
+                Some(serde_yaml::Value::Mapping(env)) => env,
+                _ => serde_yaml::Mapping::new(),
+            };
+            for (name, value) in vars {
+                env.insert(serde_yaml::Value::String(name.clone()), value.clone());
+            }
+            step.insert(env_key, serde_yaml::Value::Mapping(env));
+        }
+    }
+
+    // Expands `pipeline`'s `matrix` and `parallelism` into the individual instances it
+    // describes, naming each `<name>-<instance-number>` and capping the total at
+    // `instance_max` (logging when a deployment's limits actually bite). Returns `None` if
+    // `pipeline` uses neither field, signalling the caller to pass the document through
+    // unchanged. Returns `Some(vec![])` if it does use one of them but the result is zero
+    // instances (e.g. an empty matrix axis, or `parallelism: 0`) — distinct from `None`,
+    // since here the document really should disappear rather than pass through unexpanded.
+    pub fn expand_pipeline(
+        pipeline: &Pipeline,
+        parallelism_max: usize,
+        instance_max: usize,
+    ) -> Option<Vec<Pipeline>> {
+        if pipeline.matrix.is_none() && pipeline.parallelism.is_none() {
+            return None;
+        }
+
+        let axis_combos = match &pipeline.matrix {
+            Some(matrix) => matrix_combinations(matrix),
+            None => vec![indexmap::IndexMap::new()],
+        };
+
+        let parallelism = match pipeline.parallelism {
+            Some(mut value) => {
+                if value > parallelism_max {
+                    println!(
+                        "drone::expand_pipeline: limiting parallelism value to {}",
+                        parallelism_max
+                    );
+                    value = parallelism_max;
+                }
+                value
+            }
+            None => 1,
+        };
+
+        let total = axis_combos.len() * parallelism;
+        if total > instance_max {
+            println!(
+                "drone::expand_pipeline: capping generated pipelines for '{}' from {} to {}",
+                pipeline.name, total, instance_max
+            );
+        }
+
+        let mut instances = Vec::new();
+        let mut generated = 0;
+        'combos: for combo in &axis_combos {
+            for _ in 0..parallelism {
+                if generated >= instance_max {
+                    break 'combos;
+                }
+                generated += 1;
+
+                let mut instance = pipeline.clone();
+                instance.name += &format!("-{}", generated);
+                instance.parallelism = None;
+                instance.matrix = None;
+                if !combo.is_empty() {
+                    inject_environment(&mut instance.extra, combo);
+                }
+                instances.push(instance);
+            }
+        }
+        Some(instances)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn value(s: &str) -> serde_yaml::Value {
+            serde_yaml::Value::String(s.to_string())
+        }
+
+        #[test]
+        fn matrix_combinations_computes_the_cartesian_product() {
+            let mut matrix = indexmap::IndexMap::new();
+            matrix.insert("BOARD".to_string(), vec![value("native"), value("nucleo")]);
+            matrix.insert("TOOLCHAIN".to_string(), vec![value("gcc")]);
+
+            let combos = matrix_combinations(&matrix);
+
+            assert_eq!(combos.len(), 2);
+            assert_eq!(combos[0].get("BOARD"), Some(&value("native")));
+            assert_eq!(combos[0].get("TOOLCHAIN"), Some(&value("gcc")));
+            assert_eq!(combos[1].get("BOARD"), Some(&value("nucleo")));
+            assert_eq!(combos[1].get("TOOLCHAIN"), Some(&value("gcc")));
+        }
+
+        #[test]
+        fn inject_environment_creates_and_merges_step_environments() {
+            let mut vars = indexmap::IndexMap::new();
+            vars.insert("BOARD".to_string(), value("native"));
+
+            let yaml = r#"
+steps:
+  - name: build
+    environment:
+      EXISTING: kept
+  - name: no-env
+"#;
+            let mut extra: indexmap::IndexMap<String, serde_yaml::Value> =
+                serde_yaml::from_str(yaml).unwrap();
+
+            inject_environment(&mut extra, &vars);
+
+            let steps = match extra.get("steps").unwrap() {
+                serde_yaml::Value::Sequence(steps) => steps,
+                _ => panic!("expected a sequence"),
+            };
+
+            let build_env = match &steps[0] {
+                serde_yaml::Value::Mapping(step) => {
+                    step.get(&value("environment")).unwrap().clone()
+                }
+                _ => panic!("expected a mapping"),
+            };
+            assert_eq!(build_env.get(&value("EXISTING")), Some(&value("kept")));
+            assert_eq!(build_env.get(&value("BOARD")), Some(&value("native")));
+
+            let no_env = match &steps[1] {
+                serde_yaml::Value::Mapping(step) => {
+                    step.get(&value("environment")).unwrap().clone()
+                }
+                _ => panic!("expected a mapping"),
+            };
+            assert_eq!(no_env.get(&value("BOARD")), Some(&value("native")));
+        }
+
+        #[test]
+        fn expand_pipeline_returns_none_when_neither_matrix_nor_parallelism_is_set() {
+            let pipeline = Pipeline {
+                kind: "pipeline".to_string(),
+                name: "build".to_string(),
+                parallelism: None,
+                matrix: None,
+                type_: "docker".to_string(),
+                extra: indexmap::IndexMap::new(),
+            };
+
+            assert!(expand_pipeline(&pipeline, 64, 256).is_none());
+        }
+
+        #[test]
+        fn expand_pipeline_caps_matrix_times_parallelism_at_instance_max() {
+            let mut matrix = indexmap::IndexMap::new();
+            matrix.insert("BOARD".to_string(), vec![value("a"), value("b"), value("c")]);
+
+            let pipeline = Pipeline {
+                kind: "pipeline".to_string(),
+                name: "build".to_string(),
+                parallelism: Some(2),
+                matrix: Some(matrix),
+                type_: "docker".to_string(),
+                extra: indexmap::IndexMap::new(),
+            };
+
+            // 3 matrix axis values * 2 parallelism = 6 total instances, capped to 4.
+            let instances = expand_pipeline(&pipeline, 64, 4).unwrap();
+
+            assert_eq!(instances.len(), 4);
+            assert_eq!(instances[0].name, "build-1");
+            assert_eq!(instances[3].name, "build-4");
+            assert!(instances.iter().all(|i| i.parallelism.is_none() && i.matrix.is_none()));
+        }
+
+        #[test]
+        fn expand_pipeline_limits_parallelism_to_parallelism_max() {
+            let pipeline = Pipeline {
+                kind: "pipeline".to_string(),
+                name: "build".to_string(),
+                parallelism: Some(10),
+                matrix: None,
+                type_: "docker".to_string(),
+                extra: indexmap::IndexMap::new(),
+            };
+
+            let instances = expand_pipeline(&pipeline, 3, 256).unwrap();
+
+            assert_eq!(instances.len(), 3);
+        }
+
+        #[test]
+        fn expand_pipeline_returns_some_empty_for_an_empty_valued_matrix_axis() {
+            // An axis with no values is distinct from no matrix at all: the document
+            // opted into expansion, so it should expand to zero instances rather than
+            // pass through with its `matrix:`/`parallelism:` keys still attached.
+            let mut matrix = indexmap::IndexMap::new();
+            matrix.insert("BOARD".to_string(), Vec::new());
+
+            let pipeline = Pipeline {
+                kind: "pipeline".to_string(),
+                name: "build".to_string(),
+                parallelism: None,
+                matrix: Some(matrix),
+                type_: "docker".to_string(),
+                extra: indexmap::IndexMap::new(),
+            };
+
+            let instances = expand_pipeline(&pipeline, 64, 256);
+            assert!(instances.is_some());
+            assert!(instances.unwrap().is_empty());
+        }
+
+        #[test]
+        fn expand_pipeline_returns_some_empty_for_parallelism_zero() {
+            let pipeline = Pipeline {
+                kind: "pipeline".to_string(),
+                name: "build".to_string(),
+                parallelism: Some(0),
+                matrix: None,
+                type_: "docker".to_string(),
+                extra: indexmap::IndexMap::new(),
+            };
+
+            let instances = expand_pipeline(&pipeline, 64, 256);
+            assert!(instances.is_some());
+            assert!(instances.unwrap().is_empty());
+        }
+    }
+}
+
+// Query parameters accepted on `/convert`, e.g. `?strict=true`.
+#[derive(serde_derive::Deserialize)]
+struct ConvertQuery {
+    #[serde(default)]
+    strict: Option<bool>,
+}
+
+// A client that doesn't send Drone's signature headers at all is exactly the traffic this
+// feature exists to reject, so a missing header is an auth failure (401), not a generic
+// warp rejection (which `handle_rejection` would otherwise turn into a 500).
+fn require_header(value: Option<String>, name: &str) -> Result<String> {
+    value.ok_or_else(|| {
+        warp::reject::custom(error::Error::Unauthorized(format!("missing {} header", name)))
+    })
 }
 
-async fn convert_handler(request: drone::Request) -> Result<impl warp::reply::Reply> {
-    const PARALLELISM_MAX: usize = 64;
+// Validates the request signature against the raw body, then hands the still-unparsed
+// bytes (plus the parsed query) on so the json filter can deserialize the body as before.
+//
+// `query` is parsed here from the raw query string, rather than via `warp::query::<T>()`,
+// because that filter's rejection type is opaque to `handle_rejection` (the same reason
+// `require_header` exists instead of `warp::header::<String>()` above) — an unparsable
+// `?strict=` value should fail with our own `Error::InvalidQuery`, mapped to 400.
+async fn verify_signature(
+    path: warp::path::FullPath,
+    raw_query: String,
+    date: Option<String>,
+    digest: Option<String>,
+    sig: Option<String>,
+    body: bytes::Bytes,
+) -> Result<(ConvertQuery, bytes::Bytes)> {
+    let date = require_header(date, "date")?;
+    let digest = require_header(digest, "digest")?;
+    let sig = require_header(sig, "signature")?;
+
+    let request_target = if raw_query.is_empty() {
+        path.as_str().to_string()
+    } else {
+        format!("{}?{}", path.as_str(), raw_query)
+    };
+
+    signature::verify(&request_target, &date, &digest, &sig, &body)
+        .map_err(|e| warp::reject::custom(e))?;
+
+    let query: ConvertQuery = serde_urlencoded::from_str(&raw_query)
+        .map_err(|e| warp::reject::custom(error::Error::InvalidQuery(e)))?;
+
+    Ok((query, body))
+}
+
+// Defaults strict mode from `STRICT_MODE_DEFAULT` when the request didn't specify `?strict=`.
+fn strict_mode(query: &ConvertQuery) -> bool {
+    query.strict.unwrap_or_else(|| {
+        std::env::var("STRICT_MODE_DEFAULT")
+            .map(|v| v == "true")
+            .unwrap_or(false)
+    })
+}
+
+async fn convert_handler(
+    request: drone::Request,
+    query: ConvertQuery,
+    limits: config::Limits,
+) -> Result<impl warp::reply::Reply> {
+    let strict = strict_mode(&query);
+
     println!("drone-riot-conv: handling request");
 
     let mut result = String::new();
@@ -102,6 +712,12 @@ async fn convert_handler(request: drone::Request) -> Result<impl warp::reply::Re
         let parsed: drone::Pipeline = match serde_yaml::from_str(doc) {
             Ok(val) => val,
             Err(e) => {
+                if strict {
+                    return Err(warp::reject::custom(error::Error::StrictYamlError {
+                        index: n + 1,
+                        source: e,
+                    }));
+                }
                 println!(
                     "drone-riot-conv: warning: error parsing yaml document {}: {}. passing through.",
                     e,
@@ -112,38 +728,188 @@ async fn convert_handler(request: drone::Request) -> Result<impl warp::reply::Re
             }
         };
 
-        if let Some(mut value) = parsed.parallelism {
-            if value > PARALLELISM_MAX {
-                println!(
-                    "convert_handler: limiting parallelism value to {}",
-                    PARALLELISM_MAX
-                );
-                value = PARALLELISM_MAX;
-            }
-            for n in 1..=value {
-                let mut instance = parsed.clone();
-                instance.name += &format!("-{}", n);
-                instance.parallelism = None;
-                result += &serde_yaml::to_string(&instance)
-                    .map_err(|e| warp::reject::custom(error::Error::DroneYamlError(e)))?;
-                result += "\n";
+        // `None` means the document used neither `matrix` nor `parallelism`, so it passes
+        // through unchanged; `Some(instances)` means it opted into expansion, even if that
+        // expands to zero instances (e.g. an empty matrix axis), in which case it's simply
+        // dropped rather than emitted with its `matrix:`/`parallelism:` keys still attached.
+        let instances = drone::expand_pipeline(&parsed, limits.parallelism_max, limits.instance_max);
+        let instances = match instances {
+            Some(instances) => instances,
+            None => {
+                result += doc;
+                continue;
             }
-        } else {
-            result += doc;
+        };
+
+        for instance in &instances {
+            result += &serde_yaml::to_string(instance)
+                .map_err(|e| warp::reject::custom(error::Error::DroneYamlError(e)))?;
+            result += "\n";
         }
     }
 
     Ok(warp::reply::json(&drone::Config { data: result }))
 }
 
+// Bind address, optional TLS, the conversion engine's instance caps, and the request body
+// size limit, all read once at startup so bad input fails fast instead of degrading
+// silently on a per-request basis.
+mod config {
+    const DEFAULT_BIND_IP: &str = "127.0.0.1";
+    const DEFAULT_BIND_PORT: &str = "3030";
+    const DEFAULT_PARALLELISM_MAX: usize = 64;
+    const DEFAULT_INSTANCE_MAX: usize = 256;
+    const DEFAULT_BODY_SIZE_LIMIT: u64 = 2 * 1024 * 1024;
+
+    pub struct Tls {
+        pub cert_path: String,
+        pub key_path: String,
+    }
+
+    // The conversion engine's caps on generated pipeline instances (see `drone::expand_pipeline`).
+    #[derive(Clone, Copy)]
+    pub struct Limits {
+        pub parallelism_max: usize,
+        pub instance_max: usize,
+    }
+
+    pub struct Config {
+        pub bind_addr: std::net::SocketAddr,
+        pub limits: Limits,
+        pub body_size_limit: u64,
+        pub tls: Option<Tls>,
+    }
+
+    fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> std::result::Result<T, String>
+    where
+        T::Err: std::fmt::Display,
+    {
+        match std::env::var(name) {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| format!("invalid {} '{}': {}", name, value, e)),
+            Err(_) => Ok(default),
+        }
+    }
+
+    // Reads BIND_IP/BIND_PORT, PARALLELISM_MAX/PIPELINE_INSTANCE_MAX, MAX_BODY_SIZE, and, if
+    // set, TLS_CERT_PATH/TLS_KEY_PATH from the environment. Returns a descriptive error
+    // instead of panicking so `main` can fail fast on bad input.
+    pub fn from_env() -> std::result::Result<Config, String> {
+        let ip = std::env::var("BIND_IP").unwrap_or_else(|_| DEFAULT_BIND_IP.to_string());
+        let port = std::env::var("BIND_PORT").unwrap_or_else(|_| DEFAULT_BIND_PORT.to_string());
+        let bind_addr = format!("{}:{}", ip, port)
+            .parse::<std::net::SocketAddr>()
+            .map_err(|e| format!("invalid BIND_IP/BIND_PORT '{}:{}': {}", ip, port, e))?;
+
+        let limits = Limits {
+            parallelism_max: env_parse("PARALLELISM_MAX", DEFAULT_PARALLELISM_MAX)?,
+            instance_max: env_parse("PIPELINE_INSTANCE_MAX", DEFAULT_INSTANCE_MAX)?,
+        };
+
+        let body_size_limit = env_parse("MAX_BODY_SIZE", DEFAULT_BODY_SIZE_LIMIT)?;
+
+        let cert_path = std::env::var("TLS_CERT_PATH").ok();
+        let key_path = std::env::var("TLS_KEY_PATH").ok();
+        let tls = match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => Some(Tls { cert_path, key_path }),
+            (None, None) => None,
+            _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must both be set, or neither".into()),
+        };
+
+        Ok(Config { bind_addr, limits, body_size_limit, tls })
+    }
+}
+
+// Version and build metadata returned by `GET /version`.
+#[derive(serde_derive::Serialize)]
+struct VersionResponse {
+    version: &'static str,
+    commit: &'static str,
+}
+
 #[tokio::main]
 async fn main() {
     println!("drone-riot-conv: started");
+
+    let config = match config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("drone-riot-conv: invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let limits = config.limits;
+    let body_size_limit = config.body_size_limit;
+
     let convert = warp::post()
         .and(warp::path("convert"))
-        .and(warp::body::json())
-        .and_then(convert_handler)
+        .and(warp::path::full())
+        .and(
+            warp::filters::query::raw()
+                .or(warp::any().map(String::new))
+                .unify(),
+        )
+        .and(warp::header::optional::<String>("date"))
+        .and(warp::header::optional::<String>("digest"))
+        .and(warp::header::optional::<String>("signature"))
+        .and(warp::body::content_length_limit(body_size_limit))
+        .and(warp::body::bytes())
+        .and_then(verify_signature)
+        .and(warp::any().map(move || limits))
+        .and_then(
+            |(query, body): (ConvertQuery, bytes::Bytes), limits: config::Limits| async move {
+                serde_json::from_slice::<drone::Request>(&body)
+                    .map(|request| (request, query, limits))
+                    .map_err(|e| warp::reject::custom(error::Error::RequestBodyError(e)))
+            },
+        )
+        .and_then(
+            |(request, query, limits): (drone::Request, ConvertQuery, config::Limits)| {
+                convert_handler(request, query, limits)
+            },
+        )
+        .map(|reply| -> Box<dyn warp::Reply> { Box::new(reply) });
+
+    // Liveness/readiness/version probes for orchestrators, so they don't have to send a
+    // fake conversion request just to check whether the sidecar is up.
+    let healthz = warp::get()
+        .and(warp::path("healthz"))
+        .map(|| -> Box<dyn warp::Reply> {
+            Box::new(warp::reply::with_status("ok", warp::http::StatusCode::OK))
+        });
+    let readyz = warp::get()
+        .and(warp::path("readyz"))
+        .map(|| -> Box<dyn warp::Reply> {
+            Box::new(warp::reply::with_status("ok", warp::http::StatusCode::OK))
+        });
+    let version = warp::get()
+        .and(warp::path("version"))
+        .map(|| -> Box<dyn warp::Reply> {
+            Box::new(warp::reply::json(&VersionResponse {
+                version: env!("CARGO_PKG_VERSION"),
+                commit: env!("GIT_COMMIT"),
+            }))
+        });
+
+    let routes = convert
+        .or(healthz)
+        .or(readyz)
+        .or(version)
         .recover(error::handle_rejection);
 
-    warp::serve(convert).run(([127, 0, 0, 1], 3030)).await;
+    match config.tls {
+        Some(tls) => {
+            warp::serve(routes)
+                .tls()
+                .cert_path(tls.cert_path)
+                .key_path(tls.key_path)
+                .run(config.bind_addr)
+                .await;
+        }
+        None => {
+            warp::serve(routes).run(config.bind_addr).await;
+        }
+    }
 }